@@ -0,0 +1,61 @@
+// The binary entry point: takes a URL on the command line, fetches it
+// through whichever scheme it names, and prints what came back. This is
+// mostly a thin driver to exercise the url module end-to-end; the
+// interesting logic all lives in `url.rs`.
+mod url;
+
+use std::env;
+
+use url::{AllowedUrl, Url};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let input = args.next().unwrap_or_else(|| {
+        eprintln!("usage: rust-web-browser <url> [relative-sub-resource]");
+        std::process::exit(1);
+    });
+
+    let page = match Url::new(&input) {
+        Ok(page) => page,
+        Err(err) => {
+            eprintln!("invalid URL {input}: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    match page.fetch() {
+        Ok((content_type, body)) => {
+            println!("content-type: {content_type}");
+            print!("{}", String::from_utf8_lossy(&body));
+        }
+        Err(err) => {
+            eprintln!("error fetching {page}: {err}");
+            std::process::exit(1);
+        }
+    }
+
+    // An optional second argument stands in for a sub-resource reference
+    // the page might contain, e.g. an <img src="..."> or
+    // <script src="...">: resolve it against the page's URL, then refuse
+    // to fetch it unless it's same-origin — the same same-origin check a
+    // real browser runs before loading one. `join` only treats references
+    // containing "://" as absolute, so a bare "data:..." argument is
+    // resolved as a path relative to the page, not as a data: URL.
+    if let Some(reference) = args.next() {
+        let resolved = page
+            .join(&reference)
+            .and_then(|candidate| AllowedUrl::new(candidate, &page));
+
+        match resolved {
+            Ok(allowed) => match allowed.url().fetch() {
+                Ok((content_type, body)) => {
+                    println!("--- sub-resource {reference} ---");
+                    println!("content-type: {content_type}");
+                    print!("{}", String::from_utf8_lossy(&body));
+                }
+                Err(err) => eprintln!("error fetching sub-resource {reference}: {err}"),
+            },
+            Err(err) => eprintln!("refusing to load sub-resource {reference}: {err}"),
+        }
+    }
+}
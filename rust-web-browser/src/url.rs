@@ -1,32 +1,143 @@
 // Bringing relevant namespaces into scope.
-use std::io;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::TcpStream;
+use std::sync::Arc;
+
+use base64::Engine;
+use rustls::{ClientConnection, StreamOwned};
+
+// The set of URL schemes this engine understands. Http/Https go out
+// over the network; File, Data and ViewSource are handled locally,
+// following the split Chromium's GURL and most browser-engineering
+// tutorials make between "network" and "non-network" schemes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Scheme {
+    Http,
+    Https,
+    File,
+    Data,
+    ViewSource,
+}
+
+impl Scheme {
+    fn parse(raw: &str) -> Option<Scheme> {
+        match raw {
+            "http" => Some(Scheme::Http),
+            "https" => Some(Scheme::Https),
+            "file" => Some(Scheme::File),
+            "data" => Some(Scheme::Data),
+            "view-source" => Some(Scheme::ViewSource),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+            Scheme::File => "file",
+            Scheme::Data => "data",
+            Scheme::ViewSource => "view-source",
+        }
+    }
+
+    fn is_network(&self) -> bool {
+        matches!(self, Scheme::Http | Scheme::Https)
+    }
+}
 
 // Struct: custom data type groups related values. Like
 // a C++ struct with only data members.
-// 
+//
 // #[derive ...] auto implements traits. Gives us extra
 // functionality without having to write the code ourselves.
 // * Debug: allows us use println!("{:?}", value) for debugging.
 // * Clone: allows us make deep copies via .clone(). Like a
 // copy ctor in C++.
 //
-// A url has: a scheme, a host, a path and a port.
+// A url has: a scheme, a host, a path and a port. For non-network
+// schemes some of these fields are repurposed: `path` holds the
+// filesystem path for `file:` and the raw "mediatype[;base64],data"
+// payload for `data:`; `inner` holds the wrapped URL for
+// `view-source:` and is None everywhere else.
 #[derive(Debug, Clone)]
-struct Url {
+pub(crate) struct Url {
     // String is a growable string on the heap.
     // It's owned because the URL struct is responsible for
     // freeing the data.
-    scheme: String,
+    scheme: Scheme,
     host: String,
     path: String,
     port: u16, // 16-bit unsigned int.
+    inner: Option<Box<Url>>,
+}
+
+// The parsed reply to an HTTP request: the status line split into its
+// code and reason phrase, the headers (keyed by lowercased name so
+// lookups are case-insensitive, per the HTTP spec), and the body as
+// text.
+#[derive(Debug, Clone)]
+struct Response {
+    status: u16,
+    reason: String,
+    headers: HashMap<String, String>,
+    body: String,
+}
+
+// A live connection to a server. Plain HTTP gets a bare TcpStream,
+// HTTPS gets that same TcpStream wrapped in a TLS session. Boxing the
+// Tls variant keeps the enum itself small, since StreamOwned carries
+// the whole rustls::ClientConnection state machine inline.
+//
+// Implementing Read + Write on the enum (instead of on each variant)
+// lets callers treat a Connection exactly like any other stream,
+// without caring whether bytes are flowing in the clear or through TLS.
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.read(buf),
+            Connection::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(stream) => stream.write(buf),
+            Connection::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(stream) => stream.flush(),
+            Connection::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// Displaying a Url prints its canonical "scheme://host[:port]/path"
+// form (or the equivalent for the non-network schemes). Implementing
+// Display instead of a standalone `to_string` method also gives us
+// `url.to_string()` for free via the standard library's blanket impl.
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_canonical_string())
+    }
 }
 
 // The impl block is where methods and associated functions are
 // defined. Behavior of the URL struct lives here.
 // * new(): creates new URL from an input string.
-// * request(): Attempts to establish cxn to a url over TCP.
+// * request(): Attempts to establish cxn to a url over TCP/TLS.
 impl Url {
     // An associated function. Will be invoked as `Url::new`,
     // doesn't take self as param. Often used for ctors.
@@ -46,35 +157,65 @@ impl Url {
     // Result<T, E>, we'll either return a URL(Self) or an Error String.
     // Ok(value) ==> success: URL instance
     // Err(err) ==> failure: Error String.
-    fn new(url_input: &str) -> Result<Self, String> {
-        // 1. url_input should be in the form SCHEME://HOSTNAME/PATH.
-        // we'll deconstruct the input string and grab relevant pieces
-        // to create a URL.
-
-        // "?": unwrap if there's an Ok(value), else immediately
-        // return the Err(error).
-        // ok_or_else: Converts an Option<T> to a Result(T, E).
-        // Takes a closure(|| (){} ) "||" => anonymous func.
-        // closure is only run if Option<T> is None. In this case if split_once("://") wasn't successful
-        let (scheme, rest_of_url) = url_input
-            .split_once("://")
-            .ok_or_else(|| "URL missing scheme delimiter :// ".to_string())?;
-
-        // "format!" macro that creates a String by formating a string literal with placeholders.
-        if scheme != "http" {
-            return Err(format!(
-                "only http is supported for now, but got : {scheme}"
-            ));
+    pub(crate) fn new(url_input: &str) -> Result<Self, String> {
+        // Every URL starts "scheme:rest", but what "rest" looks like
+        // depends entirely on the scheme: http/https expect "//host/path",
+        // file expects a path, data expects "mediatype,data", and
+        // view-source expects another whole URL. So we only split off
+        // the scheme name here and hand the rest to a scheme-specific
+        // constructor.
+        let (scheme_str, rest) = url_input
+            .split_once(':')
+            .ok_or_else(|| "URL missing scheme delimiter ':'".to_string())?;
+
+        // Canonicalize the scheme to lowercase up front, matching
+        // Chromium's GURL guarantee that a parsed URL's spec is always
+        // in canonical form ("HTTP://x" and "http://x" are the same URL).
+        let scheme_str = scheme_str.to_lowercase();
+        let scheme = Scheme::parse(&scheme_str)
+            .ok_or_else(|| format!("unsupported scheme : {scheme_str}"))?;
+
+        match scheme {
+            Scheme::Http | Scheme::Https => Self::new_network(scheme, rest),
+            Scheme::File => Self::new_file(rest),
+            Scheme::Data => Self::new_data(rest),
+            Scheme::ViewSource => Self::new_view_source(rest),
         }
+    }
+
+    // Parses the "//host[:port][/path]" authority network schemes use.
+    // This is the original http-only parser from before file/data/
+    // view-source support existed, now shared by both http and https.
+    fn new_network(scheme: Scheme, rest: &str) -> Result<Self, String> {
+        let rest = rest
+            .strip_prefix("//")
+            .ok_or_else(|| "URL missing // after scheme".to_string())?;
+
+        // The default port depends on the scheme: plain HTTP talks on 80,
+        // HTTPS talks on 443. An explicit ":port" on the authority (handled
+        // below) overrides whichever default we pick here.
+        let default_port = Self::default_port_for(&scheme);
 
-        // 2. HOSTNAME /PATH
         // 'match' super powered switch, ensures we handle every possible case.
-        let (host, path) = match rest_of_url.split_once('/') {
+        let (authority, path) = match rest.split_once('/') {
             // if `split_once`` returns something, destructure the tuple.
             // we include the / for the path because it's a part of the path.
-            Some((host, path)) => (host, format!("/{}", path)),
+            Some((authority, path)) => (authority, format!("/{}", path)),
             // if `split_once` returns None, we use the rest of the url as the host and path as "/"
-            None => (rest_of_url, "/".to_string()),
+            None => (rest, "/".to_string()),
+        };
+
+        // The authority is "host" or "host:port". Split off an explicit
+        // port if one is present, otherwise fall back to the scheme's
+        // default port picked above.
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port_str)) => {
+                let port = port_str
+                    .parse::<u16>()
+                    .map_err(|_| format!("invalid port : {port_str}"))?;
+                (host, port)
+            }
+            None => (authority, default_port),
         };
 
         // Return a String error if the host is empty.
@@ -82,22 +223,607 @@ impl Url {
             return Err(String::from("host is empty"));
         }
 
+        // Canonicalize the host to lowercase ("Example.COM" and
+        // "example.com" name the same server) and percent-encode
+        // anything in the path that isn't safe to drop straight into an
+        // HTTP request line — spaces, control characters, and non-ASCII
+        // bytes (encoded as their UTF-8 %XX sequences).
+        let host = host.to_lowercase();
+        let path = percent_encode_path(&path);
+
         // Construct an return a URL(self) on success.
         Ok(Self {
-            scheme: scheme.to_string(),
-            host: host.to_string(),
+            scheme,
+            host,
             path,
-            port: 80,
+            port,
+            inner: None,
+        })
+    }
+
+    // `file:` URLs name a path on the local filesystem instead of a
+    // network host, so there's no authority to parse: "file:///a/b" and
+    // "file:/a/b" both just mean the absolute path "/a/b".
+    fn new_file(rest: &str) -> Result<Self, String> {
+        let path = rest.strip_prefix("//").unwrap_or(rest);
+        if path.is_empty() {
+            return Err("file URL missing a path".to_string());
+        }
+        let path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("/{path}")
+        };
+
+        Ok(Self {
+            scheme: Scheme::File,
+            host: String::new(),
+            path,
+            port: 0,
+            inner: None,
+        })
+    }
+
+    // `data:` URLs carry their content inline as "mediatype[;base64],data".
+    // We don't decode the payload here — that only matters once something
+    // actually fetches the URL — we just check the comma that separates
+    // the metadata from the data is present.
+    fn new_data(rest: &str) -> Result<Self, String> {
+        if !rest.contains(',') {
+            return Err("data URL missing comma separating mediatype from data".to_string());
+        }
+
+        Ok(Self {
+            scheme: Scheme::Data,
+            host: String::new(),
+            path: rest.to_string(),
+            port: 0,
+            inner: None,
+        })
+    }
+
+    // `view-source:` wraps another URL; "view-source:http://a.com" means
+    // "fetch http://a.com but show me its raw bytes". Parsing it eagerly
+    // parses the wrapped URL too, so a malformed inner URL is rejected up
+    // front instead of at fetch time.
+    fn new_view_source(rest: &str) -> Result<Self, String> {
+        let inner = Url::new(rest)?;
+
+        Ok(Self {
+            scheme: Scheme::ViewSource,
+            host: String::new(),
+            path: String::new(),
+            port: 0,
+            inner: Some(Box::new(inner)),
         })
     }
 
+    // Resolves `input` against `self` as a base URL, the same rule
+    // browsers use to turn a page's relative `href`s into fetchable
+    // URLs. Mirrors the behavior described in rust-url / librsvg:
+    // * "scheme://..." -> input is already absolute, parse it fresh.
+    // * "//host/path"   -> inherit only the scheme from the base.
+    // * "/path"         -> replace the whole path, keep scheme/host/port.
+    // * "path"          -> relative to the base's "directory", i.e. the
+    //                      base path with its last segment dropped.
+    pub(crate) fn join(&self, input: &str) -> Result<Url, String> {
+        if input.contains("://") {
+            return Url::new(input);
+        }
+
+        if !self.scheme.is_network() {
+            return Err(format!(
+                "cannot resolve a relative reference against a {} URL",
+                self.scheme.as_str()
+            ));
+        }
+
+        if let Some(rest) = input.strip_prefix("//") {
+            return Url::new(&format!("{}://{}", self.scheme.as_str(), rest));
+        }
+
+        let path = if let Some(absolute_path) = input.strip_prefix('/') {
+            format!("/{absolute_path}")
+        } else {
+            // Drop everything after the base path's final '/' to get its
+            // "directory", then append the relative reference to that.
+            let directory = match self.path.rfind('/') {
+                Some(index) => &self.path[..=index],
+                None => "/",
+            };
+            format!("{directory}{input}")
+        };
+
+        let path = Self::normalize_path(&path);
+
+        let authority = match self.port {
+            port if port == Self::default_port_for(&self.scheme) => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        };
+
+        Url::new(&format!("{}://{}{}", self.scheme.as_str(), authority, path))
+    }
+
+    // Resolves "." and ".." segments by walking the path component by
+    // component and popping the stack on "..", the same algorithm
+    // browsers use to collapse a path into its canonical form.
+    fn normalize_path(path: &str) -> String {
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+        format!("/{}", segments.join("/"))
+    }
+
+    // The port a scheme uses when none is given explicitly. Shared by
+    // `new` (to fill in a missing port) and `join` (to decide whether a
+    // port needs to be written back out explicitly).
+    fn default_port_for(scheme: &Scheme) -> u16 {
+        match scheme {
+            Scheme::Https => 443,
+            _ => 80,
+        }
+    }
+
+    // Renders the URL back into canonical text: scheme and host already
+    // came in lowercase from `new`, and here we additionally drop the
+    // port when it's just the scheme's default, so e.g.
+    // "http://example.com:80/a" and "http://example.com/a" serialize
+    // identically and can be compared as strings.
+    fn to_canonical_string(&self) -> String {
+        match &self.scheme {
+            Scheme::Http | Scheme::Https => {
+                let authority = if self.port == Self::default_port_for(&self.scheme) {
+                    self.host.clone()
+                } else {
+                    format!("{}:{}", self.host, self.port)
+                };
+                format!("{}://{}{}", self.scheme.as_str(), authority, self.path)
+            }
+            Scheme::File => format!("file://{}", self.path),
+            Scheme::Data => format!("data:{}", self.path),
+            Scheme::ViewSource => {
+                let inner = self
+                    .inner
+                    .as_ref()
+                    .expect("ViewSource URL always carries an inner URL");
+                format!("view-source:{inner}")
+            }
+        }
+    }
+
     // A method because first param is &self. &self is an immutable borrow
     // of the Url Struct, giving only read-only access.
     // io::Result<T> == io::Result<T, io::Error>, error type is a standard io::Error.
-    fn request(&self) -> io::Result<TcpStream> {
+    fn request(&self) -> io::Result<Connection> {
         // TCP: AF_INET, SOCK_STREAM , TCP
         // Takes a tuple of (host, port)
         // passes the host as a borrowed string slice
-        TcpStream::connect((self.host.as_str(), self.port))
+        let tcp_stream = TcpStream::connect((self.host.as_str(), self.port))?;
+
+        // http stays a bare TCP stream. https gets the stream wrapped in
+        // a rustls client session, using the host as the SNI server name
+        // so the remote end knows which certificate to present.
+        if self.scheme == Scheme::Https {
+            let config = Self::tls_client_config();
+            let server_name = self
+                .host
+                .as_str()
+                .to_owned()
+                .try_into()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid dns name"))?;
+            let conn = ClientConnection::new(Arc::new(config), server_name)
+                .map_err(io::Error::other)?;
+            Ok(Connection::Tls(Box::new(StreamOwned::new(
+                conn, tcp_stream,
+            ))))
+        } else {
+            Ok(Connection::Plain(tcp_stream))
+        }
+    }
+
+    // Builds the rustls ClientConfig used for every HTTPS request. Trusts
+    // the well-known web PKI roots bundled by webpki-roots, the same set
+    // shipped by most browsers, so we can verify ordinary server certs
+    // without depending on whatever CA bundle happens to be installed
+    // on the host machine.
+    fn tls_client_config() -> rustls::ClientConfig {
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+        rustls::ClientConfig::builder()
+            .with_root_certificates(root_store)
+            .with_no_client_auth()
+    }
+
+    // Opens a connection, writes a GET request line, and parses the
+    // reply into a Response. `fetch` calls this for http/https URLs;
+    // `request` just gets the socket open.
+    fn get(&self) -> io::Result<Response> {
+        let connection = self.request()?;
+        let mut reader = BufReader::new(connection);
+
+        // `Connection: close` tells the server not to keep the socket
+        // open for more requests, which keeps the "read until EOF"
+        // fallback below simple. `Host` is required by HTTP/1.1.
+        let request_line = format!(
+            "GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\nUser-Agent: rust-web-browser\r\n\r\n",
+            path = self.path,
+            host = self.host,
+        );
+        reader.get_mut().write_all(request_line.as_bytes())?;
+
+        // Status line looks like "HTTP/1.1 200 OK". Split on the first two
+        // spaces into (version, status code, reason phrase) and keep the
+        // parts we care about.
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let mut parts = status_line.trim_end().splitn(3, ' ');
+        let _version = parts
+            .next()
+            .ok_or_else(|| invalid_data("missing HTTP version in status line"))?;
+        let status = parts
+            .next()
+            .ok_or_else(|| invalid_data("missing status code in status line"))?
+            .parse::<u16>()
+            .map_err(|_| invalid_data("status code is not a number"))?;
+        let reason = parts.next().unwrap_or("").to_string();
+
+        // Headers come one per line until a blank line ends the block.
+        // Header names are case-insensitive, so we lowercase them before
+        // storing so callers can look them up with e.g. "content-length".
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid_data("malformed header line"))?;
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+
+        // Transfer-Encoding: chunked and Content-Length are mutually
+        // exclusive ways the server tells us how much body to expect.
+        // If neither is present, we fall back to reading until the
+        // connection closes (which `Connection: close` guarantees).
+        let body = if headers
+            .get("transfer-encoding")
+            .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+        {
+            read_chunked_body(&mut reader)?
+        } else if let Some(content_length) = headers.get("content-length") {
+            let content_length = content_length
+                .parse::<usize>()
+                .map_err(|_| invalid_data("content-length is not a number"))?;
+            let mut buf = vec![0u8; content_length];
+            reader.read_exact(&mut buf)?;
+            String::from_utf8_lossy(&buf).into_owned()
+        } else {
+            let mut buf = String::new();
+            reader.read_to_string(&mut buf)?;
+            buf
+        };
+
+        Ok(Response {
+            status,
+            reason,
+            headers,
+            body,
+        })
+    }
+
+    // The scheme-agnostic entry point the rest of the browser should
+    // call to load a URL: it returns the content type and body bytes
+    // whether they came off the network, off disk, or were inlined in
+    // the URL itself. File and data bodies are the raw bytes; http/https
+    // bodies pass through Response's String body, so invalid UTF-8 is
+    // replaced with U+FFFD (or, for a body read until EOF, is an error).
+    pub(crate) fn fetch(&self) -> Result<(String, Vec<u8>), String> {
+        match &self.scheme {
+            Scheme::Http | Scheme::Https => {
+                let response = self.get().map_err(|e| e.to_string())?;
+                if !(200..300).contains(&response.status) {
+                    return Err(format!(
+                        "{self} returned {} {}",
+                        response.status, response.reason
+                    ));
+                }
+                let content_type = response
+                    .headers
+                    .get("content-type")
+                    .cloned()
+                    .unwrap_or_else(|| "text/html".to_string());
+                Ok((content_type, response.body.into_bytes()))
+            }
+            Scheme::File => {
+                let path = percent_decode_path(&self.path);
+                let body = std::fs::read(&path).map_err(|e| e.to_string())?;
+                Ok(("text/html".to_string(), body))
+            }
+            Scheme::Data => {
+                let (metadata, data) = self
+                    .path
+                    .split_once(',')
+                    .ok_or_else(|| "data URL missing comma separating mediatype from data".to_string())?;
+
+                let (mediatype, is_base64) = match metadata.strip_suffix(";base64") {
+                    Some(mediatype) => (mediatype, true),
+                    None => (metadata, false),
+                };
+                let mediatype = if mediatype.is_empty() {
+                    "text/plain;charset=US-ASCII"
+                } else {
+                    mediatype
+                };
+
+                let body = if is_base64 {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(data)
+                        .map_err(|e| format!("invalid base64 in data URL : {e}"))?
+                } else {
+                    data.as_bytes().to_vec()
+                };
+
+                Ok((mediatype.to_string(), body))
+            }
+            Scheme::ViewSource => {
+                // The inner URL is whatever "view-source:" is wrapping;
+                // we fetch it normally and just relabel the result as
+                // plain text so the browser shows source instead of
+                // rendering it.
+                let inner = self
+                    .inner
+                    .as_ref()
+                    .expect("ViewSource URL always carries an inner URL");
+                let (_, body) = inner.fetch()?;
+                Ok(("text/plain".to_string(), body))
+            }
+        }
+    }
+
+    // The (scheme, host, effective-port) triple that defines this URL's
+    // origin, borrowing the concept from rust-url. Network schemes use
+    // their real host and port. Non-network schemes (file, data,
+    // view-source) have no server to key an origin off of, so browsers
+    // treat them as opaque: a file: URL is never same-origin with
+    // another file: URL, even itself-by-path, unless the two URLs are
+    // textually identical. We model that by putting the whole canonical
+    // URL in the "host" slot instead of the (empty) host field, so the
+    // tuple only compares equal for two identical URLs.
+    fn origin(&self) -> (String, String, u16) {
+        if self.scheme.is_network() {
+            (self.scheme.as_str().to_string(), self.host.clone(), self.port)
+        } else {
+            (self.scheme.as_str().to_string(), self.to_canonical_string(), 0)
+        }
+    }
+
+    // Two URLs are same-origin when their scheme, host, and effective
+    // port all match — the same check a browser runs before letting one
+    // page's script touch another page's data.
+    fn same_origin(&self, other: &Url) -> bool {
+        self.origin() == other.origin()
+    }
+}
+
+// A same-origin policy guard, in the spirit of librsvg's AllowedUrl: a
+// sub-resource URL (a script, stylesheet, or image a page references)
+// only makes it into one of these if it's same-origin with the
+// document that referenced it, or its scheme is one we trust
+// regardless of origin. Holding an AllowedUrl instead of a bare Url is
+// how the rest of the engine should require "this was already checked"
+// at the type level.
+pub(crate) struct AllowedUrl(Url);
+
+impl AllowedUrl {
+    // Schemes whose content is always inline (never fetched from a
+    // network origin), so there's no cross-origin data to leak by
+    // allowing them regardless of the referencing document's origin.
+    pub(crate) const ALLOWED_SCHEMES_ANY_ORIGIN: [Scheme; 1] = [Scheme::Data];
+
+    // Checks `candidate` against `base`'s origin and wraps it if it
+    // passes. `base` is the URL of the document doing the referencing,
+    // e.g. the page whose <img src="..."> or <script src="..."> is
+    // being resolved.
+    pub(crate) fn new(candidate: Url, base: &Url) -> Result<Self, String> {
+        if Self::ALLOWED_SCHEMES_ANY_ORIGIN.contains(&candidate.scheme) {
+            return Ok(Self(candidate));
+        }
+
+        if base.same_origin(&candidate) {
+            Ok(Self(candidate))
+        } else {
+            Err(format!(
+                "refusing to load {candidate}: does not share an origin with {base}"
+            ))
+        }
+    }
+
+    pub(crate) fn url(&self) -> &Url {
+        &self.0
+    }
+}
+
+// Decodes a `Transfer-Encoding: chunked` body: each chunk is a
+// hex-encoded size, a CRLF, that many bytes of data, then a trailing
+// CRLF, repeated until a zero-size chunk marks the end.
+fn read_chunked_body(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        let size_line = size_line.trim_end();
+        // Chunk extensions (";name=value") aren't used in practice here,
+        // but strip them off just in case a server sends one.
+        let size_str = size_line.split(';').next().unwrap_or(size_line);
+        let chunk_size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| invalid_data("invalid chunk size"))?;
+
+        if chunk_size == 0 {
+            break;
+        }
+
+        let mut chunk = vec![0u8; chunk_size];
+        reader.read_exact(&mut chunk)?;
+        body.extend_from_slice(&chunk);
+
+        // Each chunk is followed by a CRLF we need to consume before
+        // reading the next size line.
+        let mut trailing_crlf = String::new();
+        reader.read_line(&mut trailing_crlf)?;
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+// Small helper for turning a parse failure into the io::Error kind the
+// rest of this module already standardizes on.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+// Percent-encodes every byte of `path` that isn't safe to place directly
+// in an HTTP request line: ASCII letters/digits and the handful of path
+// punctuation characters RFC 3986 calls "pchar" pass through unchanged,
+// as does '?', which starts the query part of the request target;
+// everything else (spaces, control characters, and non-ASCII bytes —
+// which are encoded one UTF-8 byte at a time) becomes a %XX sequence.
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if is_path_safe_byte(byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn is_path_safe_byte(byte: u8) -> bool {
+    matches!(byte,
+        b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9'
+        | b'-' | b'.' | b'_' | b'~'
+        | b'/' | b':' | b'@'
+        | b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+        | b'?'
+        // '%' itself is left alone so percent_encode_path is idempotent:
+        // a path that already went through canonicalization (e.g. the
+        // one `join` rebuilds from `self.path` before handing it back to
+        // `Url::new`) doesn't get its existing %XX escapes re-encoded
+        // into %25XX.
+        | b'%')
+}
+
+// The inverse of `percent_encode_path`: turns %XX sequences back into
+// raw bytes. Used whenever a path needs to be read back in its original
+// form, e.g. to look up a file on disk by its decoded name.
+fn percent_decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(value) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(value);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encode_decode_round_trips() {
+        let raw = "/a b/café";
+        let encoded = percent_encode_path(raw);
+        assert_eq!(encoded, "/a%20b/caf%C3%A9");
+        assert_eq!(percent_decode_path(&encoded), raw);
+    }
+
+    #[test]
+    fn normalize_path_resolves_dot_and_dotdot_segments() {
+        assert_eq!(Url::normalize_path("/a/./b/../c"), "/a/c");
+        assert_eq!(Url::normalize_path("/a/b/.."), "/a");
+        assert_eq!(Url::normalize_path("/.."), "/");
+    }
+
+    #[test]
+    fn join_resolves_relative_and_dotdot_references() {
+        let base = Url::new("http://example.com/a/b/c").unwrap();
+        let joined = base.join("../d").unwrap();
+        assert_eq!(joined.to_string(), "http://example.com/a/d");
+    }
+
+    #[test]
+    fn join_does_not_double_encode_an_already_canonical_path() {
+        // Regression test: `join` rebuilds a URL string from `self.path`
+        // and re-parses it with `Url::new`, which re-runs
+        // `percent_encode_path`. An existing %XX escape must survive
+        // that round trip unchanged instead of becoming %25XX.
+        let base = Url::new("http://example.com/a%20b/c").unwrap();
+        let joined = base.join("d").unwrap();
+        assert_eq!(joined.path, "/a%20b/d");
+    }
+
+    #[test]
+    fn read_chunked_body_concatenates_chunks_until_zero_length() {
+        let mut reader = io::Cursor::new(b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n".as_slice());
+        let body = read_chunked_body(&mut reader).unwrap();
+        assert_eq!(body, "Wikipedia");
+    }
+
+    #[test]
+    fn same_origin_compares_scheme_host_and_port() {
+        let a = Url::new("http://example.com/a").unwrap();
+        let b = Url::new("http://example.com/b").unwrap();
+        let different_port = Url::new("http://example.com:8080/a").unwrap();
+        let different_host = Url::new("http://other.com/a").unwrap();
+
+        assert!(a.same_origin(&b));
+        assert!(!a.same_origin(&different_port));
+        assert!(!a.same_origin(&different_host));
+    }
+
+    #[test]
+    fn file_origins_are_opaque() {
+        let a = Url::new("file:///etc/passwd").unwrap();
+        let b = Url::new("file:///home/page.html").unwrap();
+        let a_again = Url::new("file:///etc/passwd").unwrap();
+
+        assert!(!a.same_origin(&b));
+        assert!(a.same_origin(&a_again));
+    }
+
+    #[test]
+    fn allowed_url_refuses_a_cross_origin_local_file() {
+        let base = Url::new("file:///home/page.html").unwrap();
+        let candidate = Url::new("file:///etc/passwd").unwrap();
+
+        assert!(AllowedUrl::new(candidate, &base).is_err());
+    }
+
+    #[test]
+    fn allowed_url_permits_an_inline_data_url_regardless_of_origin() {
+        let base = Url::new("https://example.com/").unwrap();
+        let candidate = Url::new("data:text/plain,hello").unwrap();
+
+        assert!(AllowedUrl::new(candidate, &base).is_ok());
     }
 }